@@ -4,12 +4,184 @@ use crate::pipe::CommandPipe;
 use std::{ffi::OsStr, ops};
 
 #[cfg(feature = "parser")]
-use lazy_static::lazy_static;
+type Result<T> = std::result::Result<T, crate::error::APipeError>;
+
+/// An operator recognized by the shell-grammar parser, naming which stream a
+/// [`ParsedRedirect`] applies to.
 #[cfg(feature = "parser")]
-use regex::Regex;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RedirectOp {
+    /// `<`
+    In,
+    /// `>`
+    Out,
+    /// `>>`
+    Append,
+    /// `2>`
+    ErrOut,
+}
 
+/// A single redirection lexed out of a command string, e.g. the `> out.txt`
+/// in `sort < in.txt | uniq > out.txt`.
 #[cfg(feature = "parser")]
-type Result<T> = std::result::Result<T, crate::error::APipeError>;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ParsedRedirect {
+    pub(crate) op: RedirectOp,
+    pub(crate) target: String,
+}
+
+/// The result of lexing a single pipeline stage's command string: the
+/// program, its arguments (quotes stripped, escapes resolved), and any
+/// redirections, in the order they appeared.
+#[cfg(feature = "parser")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ParsedCommand {
+    pub(crate) program: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) redirects: Vec<ParsedRedirect>,
+}
+
+#[cfg(feature = "parser")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Op(RedirectOp),
+}
+
+/// Lexes `input` into words (quotes stripped, backslash escapes resolved)
+/// and redirection operators (`<`, `>`, `>>`, `2>`).
+#[cfg(feature = "parser")]
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    let mut current = String::new();
+    let mut has_token = false;
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                if has_token {
+                    tokens.push(Token::Word(std::mem::take(&mut current)));
+                    has_token = false;
+                }
+                chars.next();
+            }
+            '\'' => {
+                // An unterminated quote is treated as implicitly closed at
+                // end-of-input rather than an error, matching the previous
+                // regex-based tokenizer.
+                chars.next();
+                has_token = true;
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    current.push(c);
+                }
+            }
+            '"' => {
+                // Same EOF tolerance as the single-quote case above.
+                chars.next();
+                has_token = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' if matches!(chars.peek(), Some('"') | Some('\\') | Some('$')) => {
+                            current.push(chars.next().unwrap());
+                        }
+                        c => current.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                chars.next();
+                has_token = true;
+                match chars.peek() {
+                    Some('"') | Some(' ') | Some('\\') => current.push(chars.next().unwrap()),
+                    // Not one of the recognized escapes: keep the backslash literally
+                    // rather than silently swallowing it.
+                    _ => current.push('\\'),
+                }
+            }
+            '<' | '>' => {
+                if c == '>' && current == "2" {
+                    current.clear();
+                    has_token = false;
+                    chars.next();
+                    tokens.push(Token::Op(RedirectOp::ErrOut));
+                    continue;
+                }
+
+                if has_token {
+                    tokens.push(Token::Word(std::mem::take(&mut current)));
+                    has_token = false;
+                }
+
+                chars.next();
+                if c == '>' {
+                    if chars.peek() == Some(&'>') {
+                        chars.next();
+                        tokens.push(Token::Op(RedirectOp::Append));
+                    } else {
+                        tokens.push(Token::Op(RedirectOp::Out));
+                    }
+                } else {
+                    tokens.push(Token::Op(RedirectOp::In));
+                }
+            }
+            c => {
+                current.push(c);
+                has_token = true;
+                chars.next();
+            }
+        }
+    }
+
+    if has_token {
+        tokens.push(Token::Word(current));
+    }
+
+    Ok(tokens)
+}
+
+/// Assembles the token stream for a single pipeline stage into a
+/// [`ParsedCommand`], pairing every redirection operator with the word
+/// immediately following it.
+#[cfg(feature = "parser")]
+fn assemble(tokens: Vec<Token>, original: &str) -> Result<ParsedCommand> {
+    let mut tokens = tokens.into_iter();
+
+    let program = match tokens.next() {
+        Some(Token::Word(program)) => program,
+        _ => return Err(crate::error::APipeError::Parser(original.to_owned())),
+    };
+
+    let mut args = Vec::new();
+    let mut redirects = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => args.push(word),
+            Token::Op(op) => match tokens.next() {
+                Some(Token::Word(target)) => redirects.push(ParsedRedirect { op, target }),
+                _ => return Err(crate::error::APipeError::Parser(original.to_owned())),
+            },
+        }
+    }
+
+    Ok(ParsedCommand {
+        program,
+        args,
+        redirects,
+    })
+}
+
+/// Parses a single pipeline stage's command string into its program,
+/// arguments and redirections.
+#[cfg(feature = "parser")]
+pub(crate) fn parse_command_str(c: &str) -> Result<ParsedCommand> {
+    assemble(tokenize(c)?, c)
+}
 
 /// Abstraction of an external command.
 ///
@@ -111,21 +283,15 @@ impl Command {
     /// # Ok(())
     /// # }
     /// ```
+    ///
+    /// Redirections (`<`, `>`, `>>`, `2>`) are lexed out but otherwise
+    /// discarded here, since a standalone [`Command`] has no boundary to wire
+    /// them to; build a [`CommandPipe`] via `TryFrom<&str>` to have them
+    /// applied.
     pub fn parse_str(c: &str) -> Result<Self> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r#"([^\s"']+)|("[^"]*?")|('[^']*?')"#).unwrap();
-        }
-
-        let matches = RE.captures_iter(c);
-        let cmd_parts: Vec<&str> = matches.map(|x| x.get(0).unwrap().as_str()).collect();
-
-        let (&cmd, args) = cmd_parts
-            .split_first()
-            .ok_or_else(|| crate::error::APipeError::Parser(c.to_owned()))?;
-
-        let command = Command::new(cmd).args(args);
+        let parsed = parse_command_str(c)?;
 
-        Ok(command)
+        Ok(Command::new(parsed.program).args(parsed.args))
     }
 }
 
@@ -142,19 +308,46 @@ mod tests {
         assert_eq!(pipe.pipeline[0].0.get_program(), "echo");
         assert_eq!(
             pipe.pipeline[0].0.get_args().collect::<Vec<&OsStr>>(),
-            &[r#""This is a test.""#]
+            &["This is a test."]
         );
         assert_eq!(pipe.pipeline[1].0.get_program(), "grep");
         assert_eq!(
             pipe.pipeline[1].0.get_args().collect::<Vec<&OsStr>>(),
-            &["-Eo", r#""\w\w\sa[^.]*""#]
+            &["-Eo", r"\w\w\sa[^.]*"]
         );
         assert_eq!(pipe.pipeline[2].0.get_program(), "sed");
         assert_eq!(
             pipe.pipeline[2].0.get_args().collect::<Vec<&OsStr>>(),
-            &[r#""s/test/TEST/""#]
+            &["s/test/TEST/"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quotes_are_stripped() -> Result<()> {
+        let cmd = Command::parse_str(r#"echo "a b" 'c d' e\ f"#)?;
+
+        assert_eq!(
+            cmd.0.get_args().collect::<Vec<&OsStr>>(),
+            &["a b", "c d", "e f"]
         );
 
         Ok(())
     }
+
+    #[test]
+    fn test_redirects_are_lexed_out_but_dropped() -> Result<()> {
+        let cmd = Command::parse_str("sort < in.txt")?;
+
+        assert_eq!(cmd.0.get_program(), "sort");
+        assert!(cmd.0.get_args().next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dangling_redirect_is_an_error() {
+        assert!(Command::parse_str("echo foo >").is_err());
+    }
 }
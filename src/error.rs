@@ -6,6 +6,12 @@ pub enum APipeError {
     Parser(String),
     ChildProcess(std::io::Error, &'static str),
     NoRunningProcesses,
+    /// A pipeline stage exited with a non-zero status while `check()` was enabled.
+    Stage {
+        index: usize,
+        code: Option<i32>,
+        stderr: Vec<u8>,
+    },
 }
 
 impl Display for APipeError {
@@ -16,6 +22,17 @@ impl Display for APipeError {
             }
             APipeError::ChildProcess(_, s) => write!(f, "{}", s),
             APipeError::NoRunningProcesses => write!(f, "No running processes."),
+            APipeError::Stage {
+                index,
+                code,
+                ref stderr,
+            } => write!(
+                f,
+                "Stage {} exited with code {}: {}",
+                index,
+                code.map_or_else(|| "<unknown>".to_owned(), |c| c.to_string()),
+                String::from_utf8_lossy(stderr)
+            ),
         }
     }
 }
@@ -76,6 +76,7 @@ pub mod cmd;
 pub mod error;
 pub mod output;
 pub mod pipe;
+pub mod redirect;
 
 #[doc(inline)]
 pub use cmd::Command;
@@ -83,3 +84,5 @@ pub use cmd::Command;
 pub use error::APipeError;
 #[doc(inline)]
 pub use pipe::CommandPipe;
+#[doc(inline)]
+pub use redirect::Redirection;
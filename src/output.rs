@@ -1,26 +1,36 @@
-use std::process;
+use std::process::ExitStatus;
 
-/// Provides a thin wrapper around [std::process::Output]
+/// The combined result of running every stage of a [`CommandPipe`](crate::CommandPipe).
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Output(process::Output);
-
-impl From<process::Output> for Output {
-    fn from(command: process::Output) -> Self {
-        Output(command)
-    }
+pub struct Output {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    statuses: Vec<ExitStatus>,
 }
 
 impl Output {
-    /// See the `status` field of [std::process::Output]
+    pub(crate) fn new(stdout: Vec<u8>, stderr: Vec<Vec<u8>>, statuses: Vec<ExitStatus>) -> Self {
+        Output {
+            stdout,
+            stderr: stderr.concat(),
+            statuses,
+        }
+    }
+
+    /// The exit code of the last stage, if any.
     pub fn status_code(&self) -> Option<i32> {
-        self.0.status.code()
+        self.statuses.last().and_then(ExitStatus::code)
     }
-    /// See the `stdout` field of [std::process::Output]
+    /// stdout captured from the last stage.
     pub fn stdout(&self) -> &[u8] {
-        self.0.stdout.as_slice()
+        self.stdout.as_slice()
     }
-    /// See the `stderr` field of [std::process::Output]
+    /// stderr captured from every stage, concatenated in pipeline order.
     pub fn stderr(&self) -> &[u8] {
-        self.0.stderr.as_slice()
+        self.stderr.as_slice()
+    }
+    /// The exit status of each stage, in pipeline order.
+    pub fn statuses(&self) -> &[ExitStatus] {
+        self.statuses.as_slice()
     }
 }
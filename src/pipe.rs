@@ -1,19 +1,66 @@
 //! An anonymous pipe.
 
-use crate::{cmd::Command, error::APipeError, output::Output};
+use crate::{cmd::Command, error::APipeError, output::Output, redirect::Redirection};
 use std::{
     ffi::OsStr,
+    io::{self, Read, Write},
     ops,
-    process::{Child, Stdio},
+    process::{Child, ChildStdout, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
 };
 
 type Result<T> = std::result::Result<T, APipeError>;
 
+/// A [`Read`](io::Read) over a streamed pipeline's last stage stdout.
+///
+/// Keeps the intermediate [`Child`] handles (and the input writer and
+/// per-stage stderr reader threads, if any) alive for as long as the reader
+/// is, and reaps them on drop.
+struct StreamingOutput {
+    stdout: ChildStdout,
+    children: Vec<Child>,
+    input_writer: Option<JoinHandle<io::Result<()>>>,
+    stderr_readers: Vec<Option<JoinHandle<io::Result<Vec<u8>>>>>,
+}
+
+impl Read for StreamingOutput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdout.read(buf)
+    }
+}
+
+impl Drop for StreamingOutput {
+    fn drop(&mut self) {
+        if let Some(writer) = self.input_writer.take() {
+            let _ = writer.join();
+        }
+
+        for child in self.children.iter_mut() {
+            let _ = child.wait();
+        }
+
+        // Nothing streamed exposes per-stage stderr, so the result is
+        // discarded, but the threads still need joining rather than leaking.
+        for reader in self.stderr_readers.drain(..).flatten() {
+            let _ = reader.join();
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 /// A type representing an anonymous pipe
 pub struct CommandPipe {
     pub(crate) pipeline: Vec<Command>,
-    last_spawned: Option<Child>,
+    running: Vec<Child>,
+    stdin_redirect: Redirection,
+    stdout_redirect: Redirection,
+    stderr_redirect: Redirection,
+    input: Option<Vec<u8>>,
+    input_writer: Option<JoinHandle<io::Result<()>>>,
+    stderr_readers: Vec<Option<JoinHandle<io::Result<Vec<u8>>>>>,
+    check: bool,
+    statuses: Vec<ExitStatus>,
+    stage_stderr: Vec<Vec<u8>>,
 }
 
 impl ops::BitOr<Command> for CommandPipe {
@@ -30,18 +77,89 @@ impl TryFrom<&str> for CommandPipe {
     type Error = APipeError;
 
     fn try_from(value: &str) -> Result<Self> {
-        let mut pipe = CommandPipe::new();
+        use crate::cmd::RedirectOp;
 
-        for cmd in value.split_terminator("|") {
-            match Command::parse_str(cmd) {
-                Ok(c) => pipe.pipeline.push(c),
-                Err(e) => return Err(e),
+        let mut pipe = CommandPipe::new();
+        let stages: Vec<&str> = value.split_terminator('|').collect();
+        let last_index = stages.len().saturating_sub(1);
+
+        for (i, stage) in stages.into_iter().enumerate() {
+            let parsed = crate::cmd::parse_command_str(stage)?;
+            pipe.pipeline
+                .push(Command::new(parsed.program).args(parsed.args));
+
+            for redirect in parsed.redirects {
+                // Only the first stage's stdin and the last stage's stdout/stderr are
+                // ever wired to anything; a redirect elsewhere has no boundary to
+                // apply to and is ignored.
+                match (i, redirect.op) {
+                    (0, RedirectOp::In) => {
+                        pipe.stdin_redirect = Redirection::File(redirect.target.into());
+                    }
+                    (i, RedirectOp::Out) if i == last_index => {
+                        pipe.stdout_redirect = Redirection::File(redirect.target.into());
+                    }
+                    (i, RedirectOp::Append) if i == last_index => {
+                        pipe.stdout_redirect = Redirection::AppendFile(redirect.target.into());
+                    }
+                    (i, RedirectOp::ErrOut) if i == last_index => {
+                        pipe.stderr_redirect = Redirection::File(redirect.target.into());
+                    }
+                    _ => {}
+                }
             }
         }
+
         Ok(pipe)
     }
 }
 
+/// Builds a pipe from raw bytes, splitting on the pipe byte (`|`) and
+/// whitespace without requiring the input to be valid UTF-8.
+///
+/// Unlike `TryFrom<&str>`, this does not understand quoting, escapes or
+/// redirections; it exists so a program or argument that isn't valid UTF-8
+/// (e.g. a path with arbitrary bytes) can still flow through the string-like
+/// API.
+#[cfg(all(feature = "parser", unix))]
+impl TryFrom<&[u8]> for CommandPipe {
+    type Error = APipeError;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut pipe = CommandPipe::new();
+
+        for stage in value.split(|&b| b == b'|') {
+            let mut words = stage
+                .split(|&b| b == b' ' || b == b'\t')
+                .filter(|word| !word.is_empty());
+
+            let program = words
+                .next()
+                .ok_or_else(|| APipeError::Parser(String::from_utf8_lossy(value).into_owned()))?;
+
+            let command =
+                Command::new(OsStr::from_bytes(program)).args(words.map(OsStr::from_bytes));
+
+            pipe.pipeline.push(command);
+        }
+
+        Ok(pipe)
+    }
+}
+
+#[cfg(all(feature = "parser", unix))]
+impl TryFrom<&OsStr> for CommandPipe {
+    type Error = APipeError;
+
+    fn try_from(value: &OsStr) -> Result<Self> {
+        use std::os::unix::ffi::OsStrExt;
+
+        CommandPipe::try_from(value.as_bytes())
+    }
+}
+
 impl CommandPipe {
     /// Create a new empty pipe.
     ///
@@ -54,7 +172,16 @@ impl CommandPipe {
     pub fn new() -> Self {
         CommandPipe {
             pipeline: Vec::new(),
-            last_spawned: None,
+            running: Vec::new(),
+            stdin_redirect: Redirection::Pipe,
+            stdout_redirect: Redirection::Pipe,
+            stderr_redirect: Redirection::Pipe,
+            input: None,
+            input_writer: None,
+            stderr_readers: Vec::new(),
+            check: false,
+            statuses: Vec::new(),
+            stage_stderr: Vec::new(),
         }
     }
 
@@ -129,8 +256,109 @@ impl CommandPipe {
         self
     }
 
+    /// Redirects the first stage's stdin instead of leaving it unconnected.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use apipe::{CommandPipe, Redirection};
+    /// let mut pipe = CommandPipe::new();
+    /// pipe.add_command("grep")
+    ///     .arg("foo")
+    ///     .stdin_redirect(Redirection::File("in.txt".into()));
+    /// ```
+    pub fn stdin_redirect(&mut self, redirect: Redirection) -> &mut Self {
+        self.stdin_redirect = redirect;
+        self
+    }
+
+    /// Redirects the last stage's stdout instead of piping it for [`output`](Self::output).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use apipe::{CommandPipe, Redirection};
+    /// let mut pipe = CommandPipe::new();
+    /// pipe.add_command("ls").stdout_redirect(Redirection::File("out.txt".into()));
+    /// ```
+    pub fn stdout_redirect(&mut self, redirect: Redirection) -> &mut Self {
+        self.stdout_redirect = redirect;
+        self
+    }
+
+    /// Redirects the last stage's stderr, e.g. [`Redirection::Merge`] for `2>&1`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use apipe::{CommandPipe, Redirection};
+    /// let mut pipe = CommandPipe::new();
+    /// pipe.add_command("ls").stderr_redirect(Redirection::Null);
+    /// ```
+    pub fn stderr_redirect(&mut self, redirect: Redirection) -> &mut Self {
+        self.stderr_redirect = redirect;
+        self
+    }
+
+    /// Feeds `input` into the first stage's stdin once the pipe is spawned.
+    ///
+    /// This lets a pipe be driven from in-memory data instead of needing an
+    /// upstream `echo`-like command. The bytes are written on a dedicated
+    /// thread so a producer that writes more than a pipe buffer's worth of
+    /// data can't deadlock against this process reading its own output.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use apipe::CommandPipe;
+    /// # fn main() -> Result<(), apipe::error::APipeError> {
+    /// let output = CommandPipe::new()
+    ///     .add_command("grep")
+    ///     .arg("foo")
+    ///     .input("foo\nbar\n")
+    ///     .spawn_with_output()?;
+    ///
+    /// assert_eq!(output.stdout(), "foo\n".as_bytes());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn input<I>(&mut self, input: I) -> &mut Self
+    where
+        I: Into<Vec<u8>>,
+    {
+        self.input = Some(input.into());
+        self
+    }
+
+    /// Opt in to `pipefail`-like behavior: [`spawn_with_output`](Self::spawn_with_output)
+    /// and [`output`](Self::output) return [`APipeError::Stage`] for the last (rightmost)
+    /// stage that exited with a non-zero status, instead of silently succeeding with that
+    /// stage's (likely empty) output.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use apipe::CommandPipe;
+    /// let mut pipe = CommandPipe::new();
+    /// pipe.add_command("false").check();
+    /// assert!(pipe.spawn_with_output().is_err());
+    /// ```
+    pub fn check(&mut self) -> &mut Self {
+        self.check = true;
+        self
+    }
+
     /// Runs the commands in the pipe.
     ///
+    /// All stages are spawned up front, with each stage's stdout wired
+    /// directly into the next stage's stdin, before any of them are waited
+    /// on. Serializing spawn-then-wait per stage would leave a producer
+    /// blocked on a full pipe buffer while its consumer hasn't started yet,
+    /// so this keeps the whole pipeline running concurrently like a shell
+    /// would. The last stage is left unwaited: its stdout is nobody's to
+    /// drain yet, so reaping it here could deadlock the same way; call
+    /// [`output`](Self::output) to drain it and finish reaping the pipeline.
+    ///
     /// ## Example
     ///
     /// ```
@@ -147,28 +375,176 @@ impl CommandPipe {
     /// # }
     /// ```
     pub fn spawn(&mut self) -> Result<()> {
-        for command in self.pipeline.iter_mut() {
-            let stdin = self.last_spawned.take().map_or(Stdio::null(), |mut std| {
-                std.stdout.take().map_or(Stdio::null(), Stdio::from)
-            });
+        let mut running = self.spawn_stages()?;
+        let last_index = running.len().saturating_sub(1);
+
+        // The last stage's stdout is left piped (unless redirected) for
+        // `output()` to read; waiting on it here, before anything drains that
+        // stdout, would deadlock once it writes more than a pipe buffer's
+        // worth of data. So only the stages before it are waited on here —
+        // `output()`'s `wait_with_output()` drains the last stage's stdout
+        // and reaps it, folding its status into `statuses` there.
+        let mut statuses = Vec::with_capacity(last_index);
+        for child in running.iter_mut().take(last_index) {
+            let status = child.wait().map_err(|e| {
+                APipeError::ChildProcess(e, "Child process exited with error code.")
+            })?;
+            statuses.push(status);
+        }
+
+        self.join_input_writer()?;
+        self.statuses = statuses;
+        self.stage_stderr = self.join_stderr_readers()?;
+        self.running = running;
+
+        Ok(())
+    }
+
+    /// Spawns every stage in the pipeline, wiring stdin/stdout between
+    /// neighbours and consulting the configured redirections and input for
+    /// the boundary stages, without waiting on any of the children.
+    fn spawn_stages(&mut self) -> Result<Vec<Child>> {
+        let mut running: Vec<Child> = Vec::with_capacity(self.pipeline.len());
+        let last_index = self.pipeline.len().saturating_sub(1);
+
+        for (i, command) in self.pipeline.iter_mut().enumerate() {
+            let stdin = if i == 0 {
+                if self.input.is_some() {
+                    Stdio::piped()
+                } else {
+                    self.stdin_redirect.clone().into_stdin()?
+                }
+            } else {
+                running
+                    .last_mut()
+                    .map_or(Stdio::null(), |child: &mut Child| {
+                        child.stdout.take().map_or(Stdio::null(), Stdio::from)
+                    })
+            };
+
+            command.0.stdin(stdin);
+
+            if i == last_index {
+                command
+                    .0
+                    .stdout(self.stdout_redirect.clone().into_stdout()?);
+                command.0.stderr(
+                    self.stderr_redirect
+                        .clone()
+                        .into_stderr(&self.stdout_redirect)?,
+                );
+            } else {
+                command.0.stdout(Stdio::piped());
+                command.0.stderr(Stdio::piped());
+            }
 
             let mut child = command
                 .0
-                .stdin(stdin)
-                .stdout(Stdio::piped())
                 .spawn()
                 .map_err(|e| APipeError::ChildProcess(e, "Failed to spawn child command"))?;
 
-            child.wait().map_err(|e| {
-                APipeError::ChildProcess(e, "Child process exited with error code.")
-            })?;
+            if i == 0 {
+                if let Some(input) = self.input.take() {
+                    let mut stdin = child
+                        .stdin
+                        .take()
+                        .expect("first stage's stdin must be piped when input is set");
+                    self.input_writer = Some(thread::spawn(move || stdin.write_all(&input)));
+                }
+            }
+
+            // Drain stderr on a dedicated thread per stage, same reasoning as the input
+            // writer: an unread stderr pipe can fill up and block a still-running stage.
+            let stderr_reader = child.stderr.take().map(|mut stderr| {
+                thread::spawn(move || {
+                    let mut buf = Vec::new();
+                    stderr.read_to_end(&mut buf).map(|_| buf)
+                })
+            });
+            self.stderr_readers.push(stderr_reader);
 
-            self.last_spawned.replace(child);
+            running.push(child);
+        }
+
+        Ok(running)
+    }
+
+    /// Joins the input-writing thread started by [`spawn_stages`](Self::spawn_stages), if any.
+    fn join_input_writer(&mut self) -> Result<()> {
+        if let Some(writer) = self.input_writer.take() {
+            let write_result = writer
+                .join()
+                .expect("input writer thread panicked while writing to pipeline stdin");
+
+            if let Err(e) = write_result {
+                if e.kind() != io::ErrorKind::BrokenPipe {
+                    return Err(APipeError::ChildProcess(
+                        e,
+                        "Failed to write input to pipeline stdin",
+                    ));
+                }
+            }
         }
 
         Ok(())
     }
 
+    /// Joins the per-stage stderr reader threads started by
+    /// [`spawn_stages`](Self::spawn_stages), returning each stage's captured stderr in
+    /// pipeline order.
+    fn join_stderr_readers(&mut self) -> Result<Vec<Vec<u8>>> {
+        self.stderr_readers
+            .drain(..)
+            .map(|reader| match reader {
+                Some(handle) => handle
+                    .join()
+                    .expect("stderr reader thread panicked")
+                    .map_err(|e| APipeError::ChildProcess(e, "Failed to read a stage's stderr")),
+                None => Ok(Vec::new()),
+            })
+            .collect()
+    }
+
+    /// Spawns the pipeline and returns a [`Read`] over the last stage's
+    /// stdout, without buffering any of it.
+    ///
+    /// Unlike [`spawn_with_output`](Self::spawn_with_output), this never
+    /// waits for the pipeline to finish before returning, so the caller can
+    /// stream gigabytes of output through a [`BufReader`](std::io::BufReader)
+    /// in constant memory. The intermediate [`Child`] handles (and the input
+    /// writer thread, if [`input`](Self::input) was used) are kept alive
+    /// behind the returned value and reaped when it is dropped.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use apipe::CommandPipe;
+    /// # use std::io::{BufRead, BufReader};
+    /// # fn main() -> Result<(), apipe::error::APipeError> {
+    /// let mut pipe = CommandPipe::new();
+    /// pipe.add_command("echo").arg("This is a test.");
+    ///
+    /// let reader = pipe.spawn_streaming()?;
+    ///
+    /// let first_line = BufReader::new(reader).lines().next().unwrap().unwrap();
+    /// assert_eq!(first_line, "This is a test.");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_streaming(mut self) -> Result<impl Read> {
+        let mut running = self.spawn_stages()?;
+        let mut last = running.pop().ok_or(APipeError::NoRunningProcesses)?;
+        let stdout = last.stdout.take().ok_or(APipeError::NoRunningProcesses)?;
+        running.push(last);
+
+        Ok(StreamingOutput {
+            stdout,
+            children: running,
+            input_writer: self.input_writer.take(),
+            stderr_readers: self.stderr_readers.drain(..).collect(),
+        })
+    }
+
     /// Spawns all commands in the pipe and returns the [`Output`].
     ///
     /// ## Example
@@ -214,11 +590,34 @@ impl CommandPipe {
     /// assert_eq!(output.stdout(), "is a test\n".as_bytes());
     /// ```
     pub fn output(&mut self) -> Result<Output> {
-        if let Some(last_proc) = self.last_spawned.take() {
-            let output = last_proc.wait_with_output().map_err(|e| {
+        if let Some(last_proc) = self.running.pop() {
+            let proc_output = last_proc.wait_with_output().map_err(|e| {
                 APipeError::ChildProcess(e, "Child process exited with error code.")
             })?;
-            return Ok(Output::from(output));
+            self.statuses.push(proc_output.status);
+
+            if self.check {
+                if let Some((index, status)) = self
+                    .statuses
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .find(|(_, status)| !status.success())
+                {
+                    let stderr = self.stage_stderr.get(index).cloned().unwrap_or_default();
+                    return Err(APipeError::Stage {
+                        index,
+                        code: status.code(),
+                        stderr,
+                    });
+                }
+            }
+
+            Ok(Output::new(
+                proc_output.stdout,
+                self.stage_stderr.clone(),
+                self.statuses.clone(),
+            ))
         } else {
             Err(APipeError::NoRunningProcesses)
         }
@@ -289,6 +688,77 @@ mod tests {
         assert_eq!(output.stdout(), "is a test\n".as_bytes());
     }
 
+    #[test]
+    fn test_input_reaches_first_stage() {
+        let output = CommandPipe::new()
+            .add_command("grep")
+            .arg("foo")
+            .input("foo\nbar\n")
+            .spawn_with_output()
+            .unwrap();
+
+        assert_eq!(output.stdout(), "foo\n".as_bytes());
+    }
+
+    #[test]
+    fn test_large_stage_output_does_not_deadlock() {
+        // Regression test for spawning every stage before waiting on any:
+        // `yes` writes far more than one pipe buffer's worth of data, so if
+        // stages were spawned and waited on one at a time, this would hang
+        // forever with `yes` blocked writing into a `head` that never started.
+        let output = CommandPipe::new()
+            .add_command("yes")
+            .add_command("head")
+            .args(["-c", "2000000"])
+            .spawn_with_output()
+            .unwrap();
+
+        assert_eq!(output.stdout().len(), 2_000_000);
+    }
+
+    #[test]
+    fn test_stderr_redirect_null_suppresses_capture() {
+        let output = CommandPipe::new()
+            .add_command("sh")
+            .args(["-c", "echo err >&2"])
+            .stderr_redirect(Redirection::Null)
+            .spawn_with_output()
+            .unwrap();
+
+        assert!(output.stderr().is_empty());
+    }
+
+    #[test]
+    fn test_stderr_redirect_inherit_suppresses_capture() {
+        let output = CommandPipe::new()
+            .add_command("sh")
+            .args(["-c", "echo err >&2"])
+            .stderr_redirect(Redirection::Inherit)
+            .spawn_with_output()
+            .unwrap();
+
+        assert!(output.stderr().is_empty());
+    }
+
+    #[test]
+    fn test_stderr_redirect_merge_appends_to_stdout_file() {
+        let path =
+            std::env::temp_dir().join(format!("apipe_test_merge_{}.txt", std::process::id()));
+
+        CommandPipe::new()
+            .add_command("sh")
+            .args(["-c", "echo out; echo err >&2"])
+            .stdout_redirect(Redirection::File(path.clone()))
+            .stderr_redirect(Redirection::Merge)
+            .spawn_with_output()
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(contents, "out\nerr\n");
+    }
+
     #[test]
     fn test_overload() {
         let mut pipe = CommandPipe::new();
@@ -305,6 +775,51 @@ mod tests {
         assert_eq!(output.stdout(), "is a test\n".as_bytes());
     }
 
+    #[test]
+    fn test_check_success_reports_statuses_and_stderr() {
+        let output = CommandPipe::new()
+            .add_command("sh")
+            .args(["-c", "echo out; echo err >&2"])
+            .add_command("cat")
+            .check()
+            .spawn_with_output()
+            .unwrap();
+
+        assert_eq!(output.stdout(), "out\n".as_bytes());
+        assert_eq!(output.stderr(), "err\n".as_bytes());
+        assert_eq!(
+            output
+                .statuses()
+                .iter()
+                .map(|s| s.success())
+                .collect::<Vec<_>>(),
+            &[true, true]
+        );
+    }
+
+    #[test]
+    fn test_check_detects_last_stage_failure() {
+        // The last stage's status isn't known until `output()` waits on it
+        // (see `spawn`'s doc comment), so this exercises that it still ends
+        // up in `statuses` in the right slot for `check()` to catch.
+        let err = CommandPipe::new()
+            .add_command("echo")
+            .arg("foo")
+            .add_command("sh")
+            .args(["-c", "exit 7"])
+            .check()
+            .spawn_with_output()
+            .unwrap_err();
+
+        match err {
+            APipeError::Stage { index, code, .. } => {
+                assert_eq!(index, 1);
+                assert_eq!(code, Some(7));
+            }
+            other => panic!("expected APipeError::Stage, got {other:?}"),
+        }
+    }
+
     #[cfg(feature = "parser")]
     #[test]
     fn test_try_from() {
@@ -343,4 +858,45 @@ mod tests {
             panic!("Shouldn't be able to parse invalid pipe!")
         };
     }
+
+    #[cfg(feature = "parser")]
+    #[test]
+    fn test_try_from_stdout_redirect() {
+        let path =
+            std::env::temp_dir().join(format!("apipe_test_redirect_{}.txt", std::process::id()));
+
+        let mut pipe = CommandPipe::try_from(
+            format!(r#"echo "This is a test." > {}"#, path.display()).as_str(),
+        )
+        .unwrap();
+        pipe.spawn_with_output().unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(contents, "This is a test.\n");
+    }
+
+    #[cfg(all(feature = "parser", unix))]
+    #[test]
+    fn test_try_from_bytes_preserves_non_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let pipe = CommandPipe::try_from(&b"echo \xffabc"[..]).unwrap();
+
+        assert_eq!(pipe.pipeline[0].0.get_program(), "echo");
+        assert_eq!(
+            pipe.pipeline[0].0.get_args().collect::<Vec<&OsStr>>(),
+            &[OsStr::from_bytes(b"\xffabc")]
+        );
+    }
+
+    #[cfg(all(feature = "parser", unix))]
+    #[test]
+    fn test_try_from_os_str() {
+        let mut pipe = CommandPipe::try_from(OsStr::new("echo hello | grep hello")).unwrap();
+        let output = pipe.spawn_with_output().unwrap();
+
+        assert_eq!(output.stdout(), b"hello\n");
+    }
 }
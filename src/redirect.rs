@@ -0,0 +1,103 @@
+//! Redirection of a pipeline's boundary stdin/stdout/stderr.
+
+use std::{
+    fs::OpenOptions,
+    io,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use crate::error::APipeError;
+
+type Result<T> = std::result::Result<T, APipeError>;
+
+/// Describes where a pipeline endpoint's stdin/stdout/stderr should come
+/// from or go to.
+///
+/// Only the first stage's stdin and the last stage's stdout/stderr are
+/// affected by a [`Redirection`]; every stage in between is always wired
+/// directly into its neighbour.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Redirection {
+    /// Connect to the neighbouring stage in the pipe (the default).
+    #[default]
+    Pipe,
+    /// Redirect to/from the file at `path`, truncating it when used for
+    /// output.
+    File(PathBuf),
+    /// Redirect to/from the file at `path`, appending to it instead of
+    /// truncating when used for output.
+    AppendFile(PathBuf),
+    /// Redirect to/from `/dev/null`.
+    Null,
+    /// Inherit the handle from the calling process.
+    Inherit,
+    /// Only meaningful as a `stderr` redirection: when stdout is redirected
+    /// to a file (or `/dev/null`), stderr goes to that same file, as with the
+    /// shell's `2>&1`. Stderr is not merged into a piped or inherited stdout:
+    /// with stdout left as [`Redirection::Pipe`] stderr is captured on its own
+    /// pipe, and with [`Redirection::Inherit`] it inherits the calling
+    /// process's stderr.
+    Merge,
+}
+
+fn open_for_reading(path: &Path) -> io::Result<Stdio> {
+    OpenOptions::new().read(true).open(path).map(Stdio::from)
+}
+
+fn open_for_writing(path: &Path, append: bool) -> io::Result<Stdio> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .map(Stdio::from)
+}
+
+impl Redirection {
+    pub(crate) fn into_stdin(self) -> Result<Stdio> {
+        match self {
+            Redirection::File(path) | Redirection::AppendFile(path) => open_for_reading(&path)
+                .map_err(|e| APipeError::ChildProcess(e, "Failed to open stdin redirection file")),
+            Redirection::Null => Ok(Stdio::null()),
+            Redirection::Inherit => Ok(Stdio::inherit()),
+            Redirection::Pipe | Redirection::Merge => Ok(Stdio::null()),
+        }
+    }
+
+    pub(crate) fn into_stdout(self) -> Result<Stdio> {
+        match self {
+            Redirection::File(path) => open_for_writing(&path, false)
+                .map_err(|e| APipeError::ChildProcess(e, "Failed to open stdout redirection file")),
+            Redirection::AppendFile(path) => open_for_writing(&path, true)
+                .map_err(|e| APipeError::ChildProcess(e, "Failed to open stdout redirection file")),
+            Redirection::Null => Ok(Stdio::null()),
+            Redirection::Inherit => Ok(Stdio::inherit()),
+            Redirection::Pipe | Redirection::Merge => Ok(Stdio::piped()),
+        }
+    }
+
+    /// Resolves a `stderr` redirection, consulting `stdout` when `self` is
+    /// [`Redirection::Merge`] so stderr ends up in stdout's file or
+    /// `/dev/null`; a piped or inherited stdout is not shared, and stderr then
+    /// gets its own pipe or the calling process's stderr.
+    pub(crate) fn into_stderr(self, stdout: &Redirection) -> Result<Stdio> {
+        match self {
+            Redirection::Merge => match stdout {
+                Redirection::File(path) | Redirection::AppendFile(path) => {
+                    // Always append here, even if stdout itself truncates: stdout has
+                    // already created/truncated the file, so re-truncating it for
+                    // stderr would wipe what stdout just wrote.
+                    open_for_writing(path, true).map_err(|e| {
+                        APipeError::ChildProcess(e, "Failed to open stderr redirection file")
+                    })
+                }
+                Redirection::Null => Ok(Stdio::null()),
+                Redirection::Inherit => Ok(Stdio::inherit()),
+                Redirection::Pipe | Redirection::Merge => Ok(Stdio::piped()),
+            },
+            other => other.into_stdout(),
+        }
+    }
+}